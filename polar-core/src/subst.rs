@@ -0,0 +1,399 @@
+use std::collections::{HashMap, HashSet};
+
+use super::counter::Counter;
+use super::rules::*;
+use super::terms::*;
+use super::walker::{walk_param, walk_rule, walk_term, Visitor};
+
+/// The set of `Symbol`s with at least one occurrence in `term`.
+///
+/// A bare `Term` can never contain a `Rule`/`Parameter` node, so there's no
+/// binder for this function to find in scope: every `Variable` occurrence it
+/// sees is free. Use [`free_variables_in_rule`] when `term` is (or comes
+/// from) a rule whose `Parameter`s should be treated as bound.
+///
+/// `RestVariable`s are always treated as occurrences, never as binders:
+/// Polar doesn't give a `RestVariable` in a list pattern a lexical scope the
+/// way a rule `Parameter` has one, so there's nothing for it to bind over.
+pub fn free_variables(term: &Term) -> HashSet<Symbol> {
+    let mut visitor = FreeVariables::new();
+    visitor.visit_term(term);
+    visitor.free
+}
+
+/// Like [`free_variables`], but `rule`'s `Parameter`s are bound over its
+/// `body`, so a parameter's occurrences there are excluded.
+pub fn free_variables_in_rule(rule: &Rule) -> HashSet<Symbol> {
+    let mut visitor = FreeVariables::new();
+    visitor.visit_rule(rule);
+    visitor.free
+}
+
+/// Collects free variables, tracking bound ones via a scope stack pushed by
+/// `visit_rule` (rule `Parameter`s are bound over the rule `body`). The
+/// scope stack only ever has an entry pushed when a `Rule` is actually
+/// visited through `visit_rule`; a bare `Term` passed to `visit_term`
+/// bypasses it entirely, by construction.
+struct FreeVariables {
+    bound: Vec<HashSet<Symbol>>,
+    free: HashSet<Symbol>,
+}
+
+impl FreeVariables {
+    fn new() -> Self {
+        Self {
+            bound: vec![HashSet::new()],
+            free: HashSet::new(),
+        }
+    }
+
+    fn is_bound(&self, sym: &Symbol) -> bool {
+        self.bound.iter().any(|scope| scope.contains(sym))
+    }
+
+    fn bind(&mut self, sym: &Symbol) {
+        self.bound.last_mut().unwrap().insert(sym.clone());
+    }
+}
+
+impl Visitor for FreeVariables {
+    fn visit_variable(&mut self, v: &Symbol) -> Option<Symbol> {
+        if !self.is_bound(v) {
+            self.free.insert(v.clone());
+        }
+        None
+    }
+
+    fn visit_rest_variable(&mut self, r: &Symbol) -> Option<Symbol> {
+        if !self.is_bound(r) {
+            self.free.insert(r.clone());
+        }
+        None
+    }
+
+    fn visit_param(&mut self, p: &Parameter) -> Option<Parameter> {
+        if let Some(sym) = bound_symbol(p) {
+            self.bind(&sym);
+        }
+        walk_param(self, p)
+    }
+
+    fn visit_rule(&mut self, r: &Rule) -> Option<Rule> {
+        self.bound.push(HashSet::new());
+        let result = walk_rule(self, r);
+        self.bound.pop();
+        result
+    }
+}
+
+/// The symbol a rule `Parameter` binds over the rule body, if any.
+fn bound_symbol(param: &Parameter) -> Option<Symbol> {
+    match param.parameter.value() {
+        Value::Variable(sym) | Value::RestVariable(sym) => Some(sym.clone()),
+        _ => None,
+    }
+}
+
+/// Replace free occurrences of `Value::Variable`s in `term` with their
+/// bindings in `env`.
+///
+/// A bare `Term` can't contain a `Rule`/`Parameter` node, so every
+/// `Variable` occurrence in it is free and eligible for substitution; there
+/// is no capture to avoid here; for that, see [`substitute_in_rule`].
+pub fn substitute(term: &Term, env: &HashMap<Symbol, Term>) -> Term {
+    let mut visitor = Substitute {
+        env,
+        bound: vec![HashMap::new()],
+        counter: Counter::new(),
+        avoid: HashSet::new(),
+    };
+    visitor.visit_term(term).unwrap_or_else(|| term.clone())
+}
+
+/// Replace free occurrences of `Value::Variable`s in `rule`'s body with
+/// their bindings in `env`, leaving occurrences bound by `rule`'s own
+/// `Parameter`s untouched.
+///
+/// Before descending under a `Parameter` whose bound symbol also appears
+/// among the free variables of some replacement term in `env`, the bound
+/// symbol is alpha-renamed to a fresh one throughout its scope so the
+/// replacement can't be captured. `counter` is the knowledge base's shared
+/// gensym counter (see [`Counter`]) — passing the same `Counter` into every
+/// call, the same way `freshen` expects, is what keeps renamed variables
+/// globally unique rather than just unique within this one call.
+pub fn substitute_in_rule(rule: &Rule, env: &HashMap<Symbol, Term>, counter: &Counter) -> Rule {
+    let mut avoid = free_variables_in_rule(rule);
+    for replacement in env.values() {
+        avoid.extend(free_variables(replacement));
+    }
+    let mut visitor = Substitute {
+        env,
+        bound: vec![HashMap::new()],
+        counter: counter.clone(),
+        avoid,
+    };
+    visitor.visit_rule(rule).unwrap_or_else(|| rule.clone())
+}
+
+struct Substitute<'a> {
+    env: &'a HashMap<Symbol, Term>,
+    /// Each scope maps a symbol bound in it to the name it's known by inside
+    /// the scope: itself, unless renaming was needed to avoid capture.
+    bound: Vec<HashMap<Symbol, Symbol>>,
+    counter: Counter,
+    /// Names a freshly generated symbol must not collide with: every free
+    /// variable already present in the rule or in `env`'s replacement
+    /// terms. A shared monotonic `Counter` means two fresh names are never
+    /// equal to *each other*, but says nothing about colliding with a name
+    /// the user happened to write by hand (e.g. a free `x_1`) — this guards
+    /// against that.
+    avoid: HashSet<Symbol>,
+}
+
+impl<'a> Substitute<'a> {
+    fn fresh(&mut self, sym: &Symbol) -> Symbol {
+        loop {
+            let candidate = Symbol(format!("{}_{}", sym.0, self.counter.next()));
+            if !self.avoid.contains(&candidate) {
+                return candidate;
+            }
+        }
+    }
+
+    fn lookup(&self, sym: &Symbol) -> Option<Symbol> {
+        self.bound.iter().rev().find_map(|scope| scope.get(sym).cloned())
+    }
+
+    /// Bind `sym` in the current scope, renaming it first if some
+    /// replacement term in `env` would otherwise be captured by it.
+    fn enter_binder(&mut self, sym: &Symbol) -> Symbol {
+        let captures = self
+            .env
+            .values()
+            .any(|replacement| free_variables(replacement).contains(sym));
+        let resolved = if captures { self.fresh(sym) } else { sym.clone() };
+        self.bound.last_mut().unwrap().insert(sym.clone(), resolved.clone());
+        resolved
+    }
+}
+
+impl<'a> Visitor for Substitute<'a> {
+    fn visit_term(&mut self, term: &Term) -> Option<Term> {
+        match term.value() {
+            Value::Variable(sym) => match self.lookup(sym) {
+                Some(resolved) if resolved != *sym => {
+                    Some(term.clone_with_value(Value::Variable(resolved)))
+                }
+                Some(_) => None,
+                None => self.env.get(sym).cloned(),
+            },
+            Value::RestVariable(sym) => match self.lookup(sym) {
+                Some(resolved) if resolved != *sym => {
+                    Some(term.clone_with_value(Value::RestVariable(resolved)))
+                }
+                Some(_) => None,
+                None => self.env.get(sym).cloned(),
+            },
+            _ => walk_term(self, term),
+        }
+    }
+
+    fn visit_param(&mut self, p: &Parameter) -> Option<Parameter> {
+        if let Some(sym) = bound_symbol(p) {
+            self.enter_binder(&sym);
+        }
+        walk_param(self, p)
+    }
+
+    fn visit_rule(&mut self, r: &Rule) -> Option<Rule> {
+        self.bound.push(HashMap::new());
+        let result = walk_rule(self, r);
+        self.bound.pop();
+        result
+    }
+}
+
+/// Alpha-rename every variable a rule binds (each `Parameter` whose term is a
+/// bare `Variable` or `RestVariable`) to a globally unique name, so the rule
+/// can be inlined or unified against without colliding with variables
+/// already in scope at the call site. Variables nested inside a parameter
+/// that is some other pattern (e.g. a list with a `RestVariable` in it) are
+/// not renamed.
+///
+/// "Globally unique" means unique across every `freshen`/`substitute_in_rule`
+/// call in the knowledge base, not just within this one call — so `counter`
+/// must be the knowledge base's single shared gensym [`Counter`]; passing a
+/// fresh `Counter` per call defeats the purpose, since two separately
+/// freshened rules would then both mint `x_0`, `x_1`, … and collide the
+/// moment they're inlined into the same scope.
+pub fn freshen(rule: &Rule, counter: &Counter) -> Rule {
+    let mut visitor = Freshen {
+        bound: vec![HashMap::new()],
+        counter: counter.clone(),
+        avoid: free_variables_in_rule(rule),
+    };
+    visitor.visit_rule(rule).unwrap_or_else(|| rule.clone())
+}
+
+struct Freshen {
+    bound: Vec<HashMap<Symbol, Symbol>>,
+    counter: Counter,
+    /// Names a freshly generated symbol must not collide with — see
+    /// `Substitute::avoid`, which guards the same thing for the same reason.
+    avoid: HashSet<Symbol>,
+}
+
+impl Freshen {
+    fn fresh(&mut self, sym: &Symbol) -> Symbol {
+        loop {
+            let candidate = Symbol(format!("{}_{}", sym.0, self.counter.next()));
+            if !self.avoid.contains(&candidate) {
+                return candidate;
+            }
+        }
+    }
+
+    fn lookup(&self, sym: &Symbol) -> Option<Symbol> {
+        self.bound.iter().rev().find_map(|scope| scope.get(sym).cloned())
+    }
+}
+
+impl Visitor for Freshen {
+    fn visit_variable(&mut self, v: &Symbol) -> Option<Symbol> {
+        self.lookup(v)
+    }
+
+    fn visit_rest_variable(&mut self, r: &Symbol) -> Option<Symbol> {
+        self.lookup(r)
+    }
+
+    fn visit_param(&mut self, p: &Parameter) -> Option<Parameter> {
+        if let Some(sym) = bound_symbol(p) {
+            let fresh = self.fresh(&sym);
+            self.bound.last_mut().unwrap().insert(sym, fresh);
+        }
+        walk_param(self, p)
+    }
+
+    fn visit_rule(&mut self, r: &Rule) -> Option<Rule> {
+        self.bound.push(HashMap::new());
+        let result = walk_rule(self, r);
+        self.bound.pop();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_variables() {
+        let term = term!(op!(And, term!(sym!("x")), term!(call!("f", [sym!("y")]))));
+        let free = free_variables(&term);
+        assert_eq!(free, hashset! { sym!("x"), sym!("y") });
+    }
+
+    #[test]
+    fn test_free_variables_excludes_bound_params() {
+        let rule = rule!("a", ["x"] => term!(op!(And, term!(sym!("x")), term!(sym!("y")))));
+        let free = free_variables_in_rule(&rule);
+        assert_eq!(free, hashset! { sym!("y") });
+    }
+
+    #[test]
+    fn test_free_variables_on_bare_term_ignores_rule_params() {
+        // A bare `Term` has no rule scope, so `free_variables` (as opposed
+        // to `free_variables_in_rule`) can't know `x` would be bound if this
+        // body were attached to the rule above.
+        let body = term!(op!(And, term!(sym!("x")), term!(sym!("y"))));
+        let free = free_variables(&body);
+        assert_eq!(free, hashset! { sym!("x"), sym!("y") });
+    }
+
+    #[test]
+    fn test_free_variables_treats_rest_variable_as_an_occurrence() {
+        let term = term!(Value::List(vec![
+            term!(value!(1)),
+            term!(Value::RestVariable(sym!("rest"))),
+        ]));
+        assert_eq!(free_variables(&term), hashset! { sym!("rest") });
+    }
+
+    #[test]
+    fn test_substitute_replaces_free_variable() {
+        let term = term!(op!(And, term!(sym!("x")), term!(sym!("y"))));
+        let env = hashmap! { sym!("x") => term!(1) };
+        let substituted = substitute(&term, &env);
+        assert_eq!(
+            substituted,
+            term!(op!(And, term!(1), term!(sym!("y"))))
+        );
+    }
+
+    #[test]
+    fn test_substitute_in_rule_avoids_capture() {
+        // (x) => x and y  with  y := x  should not let the rule's own `x`
+        // parameter capture the replacement's free `x`.
+        let rule = rule!("a", ["x"] => term!(op!(And, term!(sym!("x")), term!(sym!("y")))));
+        let env = hashmap! { sym!("y") => term!(sym!("x")) };
+        let substituted = substitute_in_rule(&rule, &env, &Counter::new());
+        // the bound `x` was renamed, so the two `x`s no longer refer to the
+        // same variable, and the parameter was renamed to match its (still
+        // bound) use in the body
+        match substituted.body.value() {
+            Value::Expression(Operation { args, .. }) => {
+                assert_ne!(args[0], term!(sym!("x")));
+                assert_eq!(args[0], substituted.params[0].parameter);
+                assert_eq!(args[1], term!(sym!("x")));
+            }
+            _ => panic!("expected an And expression"),
+        }
+    }
+
+    #[test]
+    fn test_freshen_renames_bound_variables() {
+        let rule = rule!("a", ["x"] => term!(op!(And, term!(sym!("x")), term!(sym!("y")))));
+        let freshened = freshen(&rule, &Counter::new());
+        match freshened.body.value() {
+            Value::Expression(Operation { args, .. }) => {
+                assert_ne!(args[0], term!(sym!("x")));
+                // free variables are left alone
+                assert_eq!(args[1], term!(sym!("y")));
+            }
+            _ => panic!("expected an And expression"),
+        }
+        assert_ne!(freshened.params[0].parameter, rule.params[0].parameter);
+    }
+
+    #[test]
+    fn test_freshen_is_globally_unique_across_calls_sharing_a_counter() {
+        // Two calls to `freshen` that share one `Counter` (as the knowledge
+        // base does) must never mint the same name, or inlining both
+        // freshened rules into the same scope would recollide exactly the
+        // variables freshening was meant to separate.
+        let rule = rule!("a", ["x"] => term!(sym!("x")));
+        let counter = Counter::new();
+        let first = freshen(&rule, &counter);
+        let second = freshen(&rule, &counter);
+        assert_ne!(first.params[0].parameter, second.params[0].parameter);
+    }
+
+    #[test]
+    fn test_freshen_avoids_colliding_with_an_existing_free_variable() {
+        // The rule already mentions a free `x_0` (whatever name the first
+        // `counter.next()` would produce); freshening `x` must not pick that
+        // name out from under it.
+        let rule = rule!("a", ["x"] => term!(op!(And, term!(sym!("x")), term!(sym!("x_0")))));
+        let counter = Counter::new();
+        let freshened = freshen(&rule, &counter);
+        match freshened.body.value() {
+            Value::Expression(Operation { args, .. }) => {
+                assert_eq!(freshened.params[0].parameter, args[0]);
+                assert_ne!(args[0], term!(sym!("x_0")));
+                assert_eq!(args[1], term!(sym!("x_0")), "the pre-existing free `x_0` must be untouched");
+            }
+            _ => panic!("expected an And expression"),
+        }
+    }
+}
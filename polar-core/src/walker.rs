@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use super::partial::Constraints;
 use super::rules::*;
 use super::terms::*;
@@ -6,7 +8,8 @@ use super::terms::*;
 ///
 /// 1. Renaming variables: &Symbol -> Option<Symbol>
 /// 2. Rewriting terms: &Operation -> Option<Term>
-/// 3. Simplifier: ???
+/// 3. Simplifier: `ScopedVisitor`, below, which tracks bound vs. free
+///    variables and guarantees evaluation-order traversal.
 
 pub trait Visitor: Sized {
     // Atoms. These may be overridden as needed.
@@ -324,6 +327,467 @@ pub fn walk_partial<V: Visitor>(visitor: &mut V, partial: &Constraints) -> Optio
     }
 }
 
+/// A read-only counterpart to [`Visitor`] for passes that only need to observe
+/// the AST (collecting variables, validating a `Constraints`, counting
+/// `Operation` nodes, ...) and have no use for the rewrite machinery's
+/// `Option<T>` return values. Modeled on syn's `Visit` trait: every method
+/// borrows its argument and returns `()`, and the default implementations
+/// simply call the matching `walk_*` function so overriding a single method
+/// still visits the rest of the tree.
+pub trait Visit<'a>: Sized {
+    // Atoms. These may be overridden as needed.
+    fn visit_number(&mut self, _n: &'a Numeric) {}
+    fn visit_string(&mut self, _s: &'a str) {}
+    fn visit_boolean(&mut self, _b: &'a bool) {}
+    fn visit_id(&mut self, _i: &'a u64) {}
+    fn visit_name(&mut self, _n: &'a Symbol) {}
+    fn visit_variable(&mut self, _v: &'a Symbol) {}
+    fn visit_rest_variable(&mut self, _r: &'a Symbol) {}
+    fn visit_operator(&mut self, _o: &'a Operator) {}
+
+    // Compounds. If you override these, you must walk the children manually.
+    fn visit_rule(&mut self, r: &'a Rule) {
+        walk_rule_ref(self, r)
+    }
+    fn visit_term(&mut self, t: &'a Term) {
+        walk_term_ref(self, t)
+    }
+    fn visit_field(&mut self, k: &'a Symbol, v: &'a Term) {
+        walk_field_ref(self, k, v)
+    }
+    fn visit_external_instance(&mut self, e: &'a ExternalInstance) {
+        walk_external_instance_ref(self, e)
+    }
+    fn visit_instance_literal(&mut self, i: &'a InstanceLiteral) {
+        walk_instance_literal_ref(self, i)
+    }
+    fn visit_dictionary(&mut self, d: &'a Dictionary) {
+        walk_dictionary_ref(self, d)
+    }
+    fn visit_pattern(&mut self, p: &'a Pattern) {
+        walk_pattern_ref(self, p)
+    }
+    fn visit_call(&mut self, c: &'a Call) {
+        walk_call_ref(self, c)
+    }
+    #[allow(clippy::ptr_arg)]
+    fn visit_list(&mut self, l: &'a TermList) {
+        walk_list_ref(self, l)
+    }
+    fn visit_operation(&mut self, o: &'a Operation) {
+        walk_operation_ref(self, o)
+    }
+    fn visit_param(&mut self, p: &'a Parameter) {
+        walk_param_ref(self, p)
+    }
+    #[allow(clippy::ptr_arg)]
+    fn visit_params(&mut self, p: &'a [Parameter]) {
+        walk_params_ref(self, p)
+    }
+    fn visit_partial(&mut self, c: &'a Constraints) {
+        walk_partial_ref(self, c)
+    }
+}
+
+pub fn walk_rule_ref<'a, V: Visit<'a>>(visitor: &mut V, rule: &'a Rule) {
+    visitor.visit_name(&rule.name);
+    walk_params_ref(visitor, &rule.params);
+    visitor.visit_term(&rule.body);
+}
+
+pub fn walk_term_ref<'a, V: Visit<'a>>(visitor: &mut V, term: &'a Term) {
+    match term.value() {
+        Value::Number(n) => visitor.visit_number(n),
+        Value::String(s) => visitor.visit_string(s),
+        Value::Boolean(b) => visitor.visit_boolean(b),
+        Value::ExternalInstance(e) => visitor.visit_external_instance(e),
+        Value::InstanceLiteral(i) => visitor.visit_instance_literal(i),
+        Value::Dictionary(d) => visitor.visit_dictionary(d),
+        Value::Pattern(p) => visitor.visit_pattern(p),
+        Value::Call(c) => visitor.visit_call(c),
+        Value::List(l) => visitor.visit_list(l),
+        Value::Variable(v) => visitor.visit_variable(v),
+        Value::RestVariable(r) => visitor.visit_rest_variable(r),
+        Value::Expression(o) => visitor.visit_operation(o),
+        Value::Partial(p) => visitor.visit_partial(p),
+    }
+}
+
+pub fn walk_field_ref<'a, V: Visit<'a>>(visitor: &mut V, key: &'a Symbol, value: &'a Term) {
+    visitor.visit_name(key);
+    visitor.visit_term(value);
+}
+
+pub fn walk_external_instance_ref<'a, V: Visit<'a>>(
+    visitor: &mut V,
+    instance: &'a ExternalInstance,
+) {
+    visitor.visit_id(&instance.instance_id);
+    if let Some(constructor) = &instance.constructor {
+        visitor.visit_term(constructor);
+    }
+    if let Some(repr) = &instance.repr {
+        visitor.visit_string(repr);
+    }
+}
+
+pub fn walk_instance_literal_ref<'a, V: Visit<'a>>(visitor: &mut V, instance: &'a InstanceLiteral) {
+    visitor.visit_name(&instance.tag);
+    for (k, v) in &instance.fields.fields {
+        visitor.visit_field(k, v);
+    }
+}
+
+pub fn walk_dictionary_ref<'a, V: Visit<'a>>(visitor: &mut V, dict: &'a Dictionary) {
+    for (k, v) in &dict.fields {
+        visitor.visit_field(k, v);
+    }
+}
+
+pub fn walk_pattern_ref<'a, V: Visit<'a>>(visitor: &mut V, pattern: &'a Pattern) {
+    match pattern {
+        Pattern::Dictionary(dict) => visitor.visit_dictionary(dict),
+        Pattern::Instance(instance) => visitor.visit_instance_literal(instance),
+    }
+}
+
+pub fn walk_call_ref<'a, V: Visit<'a>>(visitor: &mut V, call: &'a Call) {
+    visitor.visit_name(&call.name);
+    for arg in &call.args {
+        visitor.visit_term(arg);
+    }
+    if let Some(kwargs) = &call.kwargs {
+        for (k, v) in kwargs {
+            visitor.visit_field(k, v);
+        }
+    }
+}
+
+#[allow(clippy::ptr_arg)]
+pub fn walk_list_ref<'a, V: Visit<'a>>(visitor: &mut V, list: &'a TermList) {
+    for term in list {
+        visitor.visit_term(term);
+    }
+}
+
+pub fn walk_operation_ref<'a, V: Visit<'a>>(visitor: &mut V, expr: &'a Operation) {
+    visitor.visit_operator(&expr.operator);
+    for arg in &expr.args {
+        visitor.visit_term(arg);
+    }
+}
+
+pub fn walk_param_ref<'a, V: Visit<'a>>(visitor: &mut V, param: &'a Parameter) {
+    visitor.visit_term(&param.parameter);
+    if let Some(spec) = &param.specializer {
+        visitor.visit_term(spec);
+    }
+}
+
+#[allow(clippy::ptr_arg)]
+pub fn walk_params_ref<'a, V: Visit<'a>>(visitor: &mut V, params: &'a [Parameter]) {
+    for param in params {
+        visitor.visit_param(param);
+    }
+}
+
+pub fn walk_partial_ref<'a, V: Visit<'a>>(visitor: &mut V, partial: &'a Constraints) {
+    visitor.visit_name(&partial.variable);
+    for op in &partial.operations {
+        visitor.visit_operation(op);
+    }
+}
+
+/// A by-value counterpart to [`Visitor`] for passes that already own the AST
+/// they're rewriting (the simplifier, renaming) and would otherwise pay for
+/// `walk_elements!`/`walk_fields!`'s defensive `.clone()` of every unchanged
+/// prefix and suffix. Modeled on rustc's `ast_fold`: every method takes its
+/// argument by value and returns the (possibly rewritten) value, so lists and
+/// field maps are rebuilt in a single linear pass with no prefix or suffix
+/// clones. `fold_term` still clones the `Value` out of each `Term` it folds
+/// and rebuilds the `Term` with `clone_with_value`.
+pub trait Fold: Sized {
+    // Atoms. These may be overridden as needed.
+    fn fold_number(&mut self, n: Numeric) -> Numeric {
+        n
+    }
+    fn fold_string(&mut self, s: String) -> String {
+        s
+    }
+    fn fold_boolean(&mut self, b: bool) -> bool {
+        b
+    }
+    fn fold_id(&mut self, i: u64) -> u64 {
+        i
+    }
+    fn fold_name(&mut self, n: Symbol) -> Symbol {
+        n
+    }
+    fn fold_variable(&mut self, v: Symbol) -> Symbol {
+        v
+    }
+    fn fold_rest_variable(&mut self, r: Symbol) -> Symbol {
+        r
+    }
+    fn fold_operator(&mut self, o: Operator) -> Operator {
+        o
+    }
+
+    // Compounds. If you override these, you must fold the children manually.
+    fn fold_rule(&mut self, r: Rule) -> Rule {
+        fold_rule(self, r)
+    }
+    fn fold_term(&mut self, t: Term) -> Term {
+        fold_term(self, t)
+    }
+    fn fold_field(&mut self, k: Symbol, v: Term) -> (Symbol, Term) {
+        fold_field(self, k, v)
+    }
+    fn fold_external_instance(&mut self, e: ExternalInstance) -> ExternalInstance {
+        fold_external_instance(self, e)
+    }
+    fn fold_instance_literal(&mut self, i: InstanceLiteral) -> InstanceLiteral {
+        fold_instance_literal(self, i)
+    }
+    fn fold_dictionary(&mut self, d: Dictionary) -> Dictionary {
+        fold_dictionary(self, d)
+    }
+    fn fold_pattern(&mut self, p: Pattern) -> Pattern {
+        fold_pattern(self, p)
+    }
+    fn fold_call(&mut self, c: Call) -> Call {
+        fold_call(self, c)
+    }
+    fn fold_list(&mut self, l: TermList) -> TermList {
+        fold_list(self, l)
+    }
+    fn fold_operation(&mut self, o: Operation) -> Operation {
+        fold_operation(self, o)
+    }
+    fn fold_param(&mut self, p: Parameter) -> Parameter {
+        fold_param(self, p)
+    }
+    fn fold_params(&mut self, p: Vec<Parameter>) -> Vec<Parameter> {
+        fold_params(self, p)
+    }
+    fn fold_partial(&mut self, c: Constraints) -> Constraints {
+        fold_partial(self, c)
+    }
+}
+
+macro_rules! fold_elements {
+    ($folder: expr, $method: ident, $list: expr) => {
+        $list
+            .into_iter()
+            .map(|el| $folder.$method(el))
+            .collect::<Vec<_>>()
+    };
+}
+
+macro_rules! fold_fields {
+    ($folder: expr, $method: ident, $dict: expr) => {
+        $dict
+            .into_iter()
+            .map(|(k, v)| $folder.$method(k, v))
+            .collect::<std::collections::BTreeMap<_, _>>()
+    };
+}
+
+pub fn fold_rule<F: Fold>(folder: &mut F, rule: Rule) -> Rule {
+    Rule {
+        name: folder.fold_name(rule.name),
+        params: folder.fold_params(rule.params),
+        body: folder.fold_term(rule.body),
+    }
+}
+
+pub fn fold_term<F: Fold>(folder: &mut F, term: Term) -> Term {
+    let value = term.value().clone();
+    let folded = match value {
+        Value::Number(n) => Value::Number(folder.fold_number(n)),
+        Value::String(s) => Value::String(folder.fold_string(s)),
+        Value::Boolean(b) => Value::Boolean(folder.fold_boolean(b)),
+        Value::ExternalInstance(e) => Value::ExternalInstance(folder.fold_external_instance(e)),
+        Value::InstanceLiteral(i) => Value::InstanceLiteral(folder.fold_instance_literal(i)),
+        Value::Dictionary(d) => Value::Dictionary(folder.fold_dictionary(d)),
+        Value::Pattern(p) => Value::Pattern(folder.fold_pattern(p)),
+        Value::Call(c) => Value::Call(folder.fold_call(c)),
+        Value::List(l) => Value::List(folder.fold_list(l)),
+        Value::Variable(v) => Value::Variable(folder.fold_variable(v)),
+        Value::RestVariable(r) => Value::RestVariable(folder.fold_rest_variable(r)),
+        Value::Expression(o) => Value::Expression(folder.fold_operation(o)),
+        Value::Partial(p) => Value::Partial(folder.fold_partial(p)),
+    };
+    term.clone_with_value(folded)
+}
+
+pub fn fold_field<F: Fold>(folder: &mut F, key: Symbol, value: Term) -> (Symbol, Term) {
+    (folder.fold_name(key), folder.fold_term(value))
+}
+
+pub fn fold_external_instance<F: Fold>(
+    folder: &mut F,
+    instance: ExternalInstance,
+) -> ExternalInstance {
+    ExternalInstance {
+        instance_id: folder.fold_id(instance.instance_id),
+        constructor: instance.constructor.map(|t| folder.fold_term(t)),
+        repr: instance.repr,
+    }
+}
+
+pub fn fold_instance_literal<F: Fold>(folder: &mut F, instance: InstanceLiteral) -> InstanceLiteral {
+    InstanceLiteral {
+        tag: folder.fold_name(instance.tag),
+        fields: Dictionary {
+            fields: fold_fields!(folder, fold_field, instance.fields.fields),
+        },
+    }
+}
+
+pub fn fold_dictionary<F: Fold>(folder: &mut F, dict: Dictionary) -> Dictionary {
+    Dictionary {
+        fields: fold_fields!(folder, fold_field, dict.fields),
+    }
+}
+
+pub fn fold_pattern<F: Fold>(folder: &mut F, pattern: Pattern) -> Pattern {
+    match pattern {
+        Pattern::Dictionary(dict) => Pattern::Dictionary(folder.fold_dictionary(dict)),
+        Pattern::Instance(instance) => Pattern::Instance(folder.fold_instance_literal(instance)),
+    }
+}
+
+pub fn fold_call<F: Fold>(folder: &mut F, call: Call) -> Call {
+    Call {
+        name: folder.fold_name(call.name),
+        args: fold_elements!(folder, fold_term, call.args),
+        kwargs: call.kwargs.map(|kwargs| fold_fields!(folder, fold_field, kwargs)),
+    }
+}
+
+pub fn fold_list<F: Fold>(folder: &mut F, list: TermList) -> TermList {
+    fold_elements!(folder, fold_term, list)
+}
+
+pub fn fold_operation<F: Fold>(folder: &mut F, expr: Operation) -> Operation {
+    Operation {
+        operator: folder.fold_operator(expr.operator),
+        args: fold_elements!(folder, fold_term, expr.args),
+    }
+}
+
+pub fn fold_param<F: Fold>(folder: &mut F, param: Parameter) -> Parameter {
+    Parameter {
+        parameter: folder.fold_term(param.parameter),
+        specializer: param.specializer.map(|spec| folder.fold_term(spec)),
+    }
+}
+
+pub fn fold_params<F: Fold>(folder: &mut F, params: Vec<Parameter>) -> Vec<Parameter> {
+    fold_elements!(folder, fold_param, params)
+}
+
+pub fn fold_partial<F: Fold>(folder: &mut F, partial: Constraints) -> Constraints {
+    Constraints {
+        variable: folder.fold_name(partial.variable),
+        operations: fold_elements!(folder, fold_operation, partial.operations),
+    }
+}
+
+/// A `Visitor` layer that tracks which `Symbol`s are bound at the current
+/// position and walks `Operation` arguments in Polar evaluation order, so
+/// that correctness-sensitive passes — the partial-`Constraints` simplifier
+/// foremost among them — can tell bound variables from free ones and can
+/// never reorder a short-circuiting operation's arguments.
+///
+/// The only binders in Polar are rule `Parameter`s, bound over the rule
+/// `body`; a `RestVariable` is always a free occurrence, never a binder —
+/// Polar doesn't give it a lexical scope the way a rule `Parameter` has one
+/// (the substitution/freshening passes in `subst.rs` rely on this same
+/// rule). `walk_operation`'s existing argument order already matches
+/// evaluation order for every operator Polar has today (`And`, `Or`, `Not`,
+/// unification, comparisons, ...), so `ScopedVisitor` only has to maintain
+/// the scope stack and give implementors a way to skip a subtree instead of
+/// walking into it (e.g. a `Parameter`'s specializer) — the same
+/// reverse-post-order invariant rustc's AST visitor relies on.
+pub trait ScopedVisitor: Visitor {
+    /// The stack of scopes currently in force, innermost last. Maintained by
+    /// `visit_scoped_rule`/`visit_scoped_param`; an implementor overriding
+    /// `visit_rule`/`visit_param` directly must call through to those (or
+    /// maintain this stack itself) to keep bound/free queries accurate.
+    fn scopes(&mut self) -> &mut Vec<HashSet<Symbol>>;
+
+    /// Is `sym` bound by some `Parameter` enclosing the node currently being
+    /// visited? (A rule `Parameter` can itself be a `RestVariable` pattern —
+    /// see `visit_scoped_param` — but a `RestVariable` never binds on its
+    /// own, only a `Parameter` does.)
+    fn is_bound(&mut self, sym: &Symbol) -> bool {
+        self.scopes().iter().any(|scope| scope.contains(sym))
+    }
+
+    /// Called before descending into a `Parameter`'s specializer term.
+    /// Return `false` to skip it — the specializer is left in place and not
+    /// walked at all, rather than walked-but-discarded.
+    fn visit_specializer(&mut self, _param: &Parameter) -> bool {
+        true
+    }
+
+    /// Push a fresh scope, walk `rule` with it in force, then pop it. Call
+    /// this from an overridden `Visitor::visit_rule`.
+    fn visit_scoped_rule(&mut self, rule: &Rule) -> Option<Rule> {
+        self.scopes().push(HashSet::new());
+        let result = walk_rule(self, rule);
+        self.scopes().pop();
+        result
+    }
+
+    /// Bind the parameter's variable in the current scope, then walk it —
+    /// skipping the specializer if `visit_specializer` says to. Call this
+    /// from an overridden `Visitor::visit_param`.
+    ///
+    /// The parameter's own variable term is the binding occurrence, not a
+    /// use, so it is not passed to `visit_term`/`visit_variable` — only the
+    /// binding is recorded in scope. If `param.parameter` isn't itself a
+    /// `Variable`/`RestVariable` (e.g. some other destructuring pattern), it
+    /// is walked normally, since it isn't (wholly) a binder.
+    fn visit_scoped_param(&mut self, param: &Parameter) -> Option<Parameter> {
+        let bound_here = matches!(
+            param.parameter.value(),
+            Value::Variable(_) | Value::RestVariable(_)
+        );
+        if let Value::Variable(sym) | Value::RestVariable(sym) = param.parameter.value() {
+            match self.scopes().last_mut() {
+                Some(scope) => {
+                    scope.insert(sym.clone());
+                }
+                None => {
+                    // `visit_param` reached outside any `visit_scoped_rule`;
+                    // there's no scope to bind into, so this parameter's
+                    // variable is simply untracked rather than bound.
+                }
+            }
+        }
+        let parameter = if bound_here {
+            None
+        } else {
+            self.visit_term(&param.parameter)
+        };
+        let specializer = if self.visit_specializer(param) {
+            param.specializer.as_ref().map(|spec| self.visit_term(spec))
+        } else {
+            param.specializer.as_ref().map(|_| None)
+        };
+        match (parameter, specializer) {
+            (None, None) => None,
+            (parameter, specializer) => Some(Parameter {
+                parameter: parameter.unwrap_or_else(|| param.parameter.clone()),
+                specializer: unwrap_or_default!(@opt specializer, param),
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -474,4 +938,269 @@ mod tests {
     }
 
     // TODO(gj): Add test for walking a partial.
+
+    struct TestVisit<'a> {
+        visited: Vec<&'a Value>,
+    }
+
+    impl<'a> TestVisit<'a> {
+        fn new() -> Self {
+            Self { visited: vec![] }
+        }
+        fn push(&mut self, value: &'a Value) {
+            self.visited.push(value);
+        }
+    }
+
+    impl<'a> Visit<'a> for TestVisit<'a> {
+        fn visit_term(&mut self, t: &'a Term) {
+            self.push(t.value());
+            walk_term_ref(self, t);
+        }
+    }
+
+    #[test]
+    fn test_visit_term_atomics() {
+        let number = value!(1);
+        let string = value!("Hi there!");
+        let boolean = value!(true);
+        let variable = value!(sym!("x"));
+        let rest_var = Value::RestVariable(sym!("rest"));
+        let list = Value::List(vec![
+            term!(number.clone()),
+            term!(string.clone()),
+            term!(boolean.clone()),
+            term!(variable.clone()),
+            term!(rest_var.clone()),
+        ]);
+        let term = term!(list.clone());
+        let mut v = TestVisit::new();
+        v.visit_term(&term);
+        assert_eq!(
+            v.visited,
+            vec![&list, &number, &string, &boolean, &variable, &rest_var]
+        );
+    }
+
+    #[test]
+    fn test_visit_term_external_instance_visits_constructor() {
+        let constructor = value!(1);
+        let external_instance = Value::ExternalInstance(ExternalInstance {
+            instance_id: 1,
+            constructor: Some(term!(constructor.clone())),
+            repr: None,
+        });
+        let term = term!(external_instance.clone());
+        let mut v = TestVisit::new();
+        v.visit_term(&term);
+        assert_eq!(v.visited, vec![&external_instance, &constructor]);
+    }
+
+    #[test]
+    fn test_visit_term_external_instance_visits_repr() {
+        let external_instance = Value::ExternalInstance(ExternalInstance {
+            instance_id: 1,
+            constructor: None,
+            repr: Some("an instance".to_string()),
+        });
+        let term = term!(external_instance);
+
+        struct CollectStrings<'a> {
+            visited: Vec<&'a str>,
+        }
+        impl<'a> Visit<'a> for CollectStrings<'a> {
+            fn visit_string(&mut self, s: &'a str) {
+                self.visited.push(s);
+            }
+        }
+
+        let mut v = CollectStrings { visited: vec![] };
+        v.visit_term(&term);
+        assert_eq!(v.visited, vec!["an instance"]);
+    }
+
+    struct RenameVariables;
+
+    impl Fold for RenameVariables {
+        fn fold_variable(&mut self, v: Symbol) -> Symbol {
+            Symbol(format!("{}_renamed", v.0))
+        }
+    }
+
+    #[test]
+    fn test_fold_term() {
+        let term = term!(Value::List(vec![
+            term!(value!(sym!("x"))),
+            term!(value!(1)),
+            term!(value!(sym!("y"))),
+        ]));
+        let folded = RenameVariables.fold_term(term);
+        assert_eq!(
+            folded.value(),
+            &Value::List(vec![
+                term!(value!(sym!("x_renamed"))),
+                term!(value!(1)),
+                term!(value!(sym!("y_renamed"))),
+            ])
+        );
+    }
+
+    /// Records, for every variable it visits, whether it was bound at that
+    /// point, and never descends into a `Parameter`'s specializer.
+    struct BoundChecker {
+        scopes: Vec<HashSet<Symbol>>,
+        checked: Vec<(Symbol, bool)>,
+    }
+
+    impl BoundChecker {
+        fn new() -> Self {
+            Self {
+                scopes: vec![],
+                checked: vec![],
+            }
+        }
+    }
+
+    impl Visitor for BoundChecker {
+        fn visit_variable(&mut self, v: &Symbol) -> Option<Symbol> {
+            let bound = self.is_bound(v);
+            self.checked.push((v.clone(), bound));
+            None
+        }
+        fn visit_rule(&mut self, r: &Rule) -> Option<Rule> {
+            self.visit_scoped_rule(r)
+        }
+        fn visit_param(&mut self, p: &Parameter) -> Option<Parameter> {
+            self.visit_scoped_param(p)
+        }
+    }
+
+    impl ScopedVisitor for BoundChecker {
+        fn scopes(&mut self) -> &mut Vec<HashSet<Symbol>> {
+            &mut self.scopes
+        }
+        fn visit_specializer(&mut self, _param: &Parameter) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_scoped_visitor_tracks_bound_variables() {
+        let rule = rule!("a", ["x"; instance!("Skipped")] =>
+            term!(op!(And, term!(sym!("x")), term!(sym!("y")))));
+        let mut v = BoundChecker::new();
+        v.visit_rule(&rule);
+        assert_eq!(
+            v.checked,
+            vec![(sym!("x"), true), (sym!("y"), false)],
+            "the specializer's `Skipped` instance should never have been visited, \
+             `x` is bound by the parameter, `y` is free"
+        );
+    }
+
+    #[test]
+    fn test_visit_scoped_param_without_enclosing_rule_does_not_panic() {
+        let param = Parameter {
+            parameter: term!(value!(sym!("x"))),
+            specializer: None,
+        };
+        let mut v = BoundChecker::new();
+        // No `visit_scoped_rule` pushed a scope, so `x` is simply untracked
+        // rather than bound.
+        v.visit_param(&param);
+        assert_eq!(v.checked, vec![]);
+    }
+}
+
+/// Exercises `#[derive(Visit)]` (from the `polar-derive` crate) end to end,
+/// against a stand-in type shaped like `ExternalInstance` — the real
+/// `ExternalInstance` is defined in `polar-core::terms`, which isn't part of
+/// this slice of the tree, so it can't be annotated directly here. This
+/// proves the derive isn't dead code and, in particular, that it walks an
+/// `Option<Term>` field with *no* `#[visit(call = "...")]` annotation,
+/// which is exactly the field the hand-written rewriting
+/// `walk_external_instance` skips.
+#[cfg(test)]
+mod derive_smoke_test {
+    use super::*;
+
+    #[derive(polar_derive::Visit)]
+    struct Instance {
+        instance_id: u64,
+        constructor: Option<Term>,
+        repr: Option<String>,
+    }
+
+    struct CollectTerms<'a> {
+        visited: Vec<&'a Value>,
+    }
+
+    impl<'a> Visit<'a> for CollectTerms<'a> {
+        fn visit_term(&mut self, t: &'a Term) {
+            self.visited.push(t.value());
+            walk_term_ref(self, t);
+        }
+    }
+
+    #[test]
+    fn test_derived_walk_visits_every_annotatable_field() {
+        let constructor = value!(1);
+        let instance = Instance {
+            instance_id: 7,
+            constructor: Some(term!(constructor.clone())),
+            repr: Some("an Instance".to_string()),
+        };
+        let mut v = CollectTerms { visited: vec![] };
+        walk_instance_ref(&mut v, &instance);
+        assert_eq!(v.visited, vec![&constructor]);
+    }
+
+    /// A stand-in for `Value`'s shape: an enum whose variants hold compound
+    /// AST nodes (not just the primitives `Instance` above covers), some of
+    /// them `Option`/`Vec`-wrapped. This is what `walk_enum_variants` must
+    /// get right — discarding `Shape` here would pass `&Option<Call>` where
+    /// a `&Call` is expected, a type error that would have been invisible
+    /// against a struct-only smoke test.
+    #[derive(polar_derive::Visit)]
+    enum Clause {
+        Bare(Call),
+        Maybe(Option<Call>),
+        Many(Vec<Call>),
+        Opaque(bool),
+    }
+
+    struct CollectCalls<'a> {
+        visited: Vec<&'a Symbol>,
+    }
+
+    impl<'a> Visit<'a> for CollectCalls<'a> {
+        fn visit_call(&mut self, c: &'a Call) {
+            self.visited.push(&c.name);
+        }
+    }
+
+    #[test]
+    fn test_derived_enum_walk_respects_option_and_vec_shapes() {
+        let one = call!("one", []);
+        let mut v = CollectCalls { visited: vec![] };
+        walk_clause_ref(&mut v, &Clause::Bare(one.clone()));
+        assert_eq!(v.visited, vec![&one.name]);
+
+        let mut v = CollectCalls { visited: vec![] };
+        walk_clause_ref(&mut v, &Clause::Maybe(Some(one.clone())));
+        assert_eq!(v.visited, vec![&one.name]);
+
+        let mut v = CollectCalls { visited: vec![] };
+        walk_clause_ref(&mut v, &Clause::Maybe(None));
+        assert!(v.visited.is_empty());
+
+        let two = call!("two", []);
+        let mut v = CollectCalls { visited: vec![] };
+        walk_clause_ref(&mut v, &Clause::Many(vec![one.clone(), two.clone()]));
+        assert_eq!(v.visited, vec![&one.name, &two.name]);
+
+        let mut v = CollectCalls { visited: vec![] };
+        walk_clause_ref(&mut v, &Clause::Opaque(true));
+        assert!(v.visited.is_empty());
+    }
 }
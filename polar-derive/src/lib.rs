@@ -0,0 +1,254 @@
+//! A proc-macro companion to `polar-core`'s read-only `Visit` trait, following
+//! syn's approach of generating its visitor from the AST definitions instead
+//! of hand-maintaining a `walk_*` per type. It generates `walk_*_ref`
+//! functions for `Visit` only; nothing is generated for `Visitor` or `Fold`.
+//! Hand-written walkers drift: the rewriting `walk_external_instance` in
+//! `polar-core::walker` visits only `instance_id` and ignores
+//! `ExternalInstance`'s `constructor` and `repr` fields. `#[derive(Visit)]`
+//! reads the struct/enum definition itself,
+//! so a new field or variant is walked automatically the next time the crate
+//! builds, with no per-field bookkeeping to forget.
+//!
+//! The method to invoke for a field or single-field variant is *inferred
+//! from its type*: a `Term` field calls `visit_term`, a `Vec<Term>` or
+//! `Option<Term>` field walks each element / the element if present, a
+//! `Symbol` calls `visit_name`, `Operator` calls `visit_operator`, and so on
+//! for every primitive *and* compound node the `Visit` trait has a method
+//! for (`Operation`, `Call`, `Dictionary`, `Pattern`, `InstanceLiteral`,
+//! `TermList`, `Constraints`, ...) — the `Vec`/`Option` unwrapping and the
+//! type-name lookup are independent, so a single-field enum variant holding
+//! `Option<Operation>` is walked exactly like a struct field of the same
+//! type would be. This is what guards against the drift above: a newly added
+//! `Option<Term>` field or a new single-field tuple variant is walked the
+//! moment `#[derive(Visit)]` re-expands, with no annotation required at all.
+//! Unit variants and struct-like variants get an empty match arm, so the
+//! fields of a struct-like variant are not walked; a tuple variant with more
+//! than one field makes the derive panic.
+//!
+//! Inference is necessarily ambiguous for a bare `Symbol` field, since
+//! `Visit` has three different methods for one (`visit_name`, `visit_variable`,
+//! `visit_rest_variable`) depending on what the symbol *means* in context —
+//! `Value::Variable`'s payload is a *use* of a variable, not merely a name.
+//! `#[visit(call = "...")]` overrides the inferred method for exactly that
+//! case; it is the exception, not the rule.
+//!
+//! Wiring this onto `polar-core`'s actual `Term`/`Value`/`Rule`/... requires
+//! annotating their definitions in `polar-core::terms`/`::rules`/`::partial`,
+//! which live outside this slice of the tree — `polar-core/src/walker.rs` is
+//! the only AST-definition-adjacent file present here, and it only
+//! *consumes* those types via `use super::terms::*;` without defining them.
+//! Until those modules are in reach, `polar-core::walker`'s hand-written
+//! `walk_*_ref` functions remain the source of truth; the `derive_smoke_test`
+//! module at the end of `polar-core/src/walker.rs` holds self-contained
+//! stand-ins (`Instance`, shaped like `ExternalInstance`, and the enum
+//! `Clause`) that exercise the derive end-to-end so it isn't untested,
+//! unused code.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Lit, Meta, NestedMeta, PathArguments, Type};
+
+#[proc_macro_derive(Visit, attributes(visit))]
+pub fn derive_visit(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fn_name = syn::Ident::new(
+        &format!("walk_{}_ref", to_snake_case(&name.to_string())),
+        name.span(),
+    );
+
+    let body = match &input.data {
+        Data::Struct(data) => walk_struct_fields(&data.fields),
+        Data::Enum(data) => walk_enum_variants(name, &data.variants),
+        Data::Union(_) => panic!("#[derive(Visit)] does not support unions"),
+    };
+
+    let expanded = quote! {
+        pub fn #fn_name<'a, V: crate::walker::Visit<'a>>(visitor: &mut V, node: &'a #name) {
+            #body
+        }
+    };
+    expanded.into()
+}
+
+/// How a field or single-field variant's value should be walked, derived
+/// from its type shape (`Vec<_>`, `Option<_>`, or bare).
+enum Shape {
+    Bare,
+    Vec,
+    Option,
+}
+
+fn shape_of(ty: &Type) -> (Shape, &Type) {
+    if let Type::Path(p) = ty {
+        if let Some(segment) = p.path.segments.last() {
+            if segment.ident == "Vec" || segment.ident == "Option" {
+                if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                        let shape = if segment.ident == "Vec" {
+                            Shape::Vec
+                        } else {
+                            Shape::Option
+                        };
+                        return (shape, inner);
+                    }
+                }
+            }
+        }
+    }
+    (Shape::Bare, ty)
+}
+
+/// The `Visit` method this type's occurrences should be passed to, inferred
+/// from the type's name. `None` for a type that has no corresponding `Visit`
+/// method (e.g. a `usize` or a `HashSet<_>` field comes back `None` and is
+/// left alone). Primitives that do have one are inferred: the raw `u64`
+/// `ExternalInstance::instance_id` goes to `visit_id`, a `bool` to
+/// `visit_boolean`.
+fn inferred_call(ty: &Type) -> Option<syn::Ident> {
+    let Type::Path(p) = ty else { return None };
+    let ident = &p.path.segments.last()?.ident;
+    let method = match ident.to_string().as_str() {
+        "Term" => "visit_term",
+        "Symbol" => "visit_name",
+        "Operator" => "visit_operator",
+        "Numeric" => "visit_number",
+        "bool" => "visit_boolean",
+        "String" | "str" => "visit_string",
+        "u64" => "visit_id",
+        "Operation" => "visit_operation",
+        "Call" => "visit_call",
+        "Dictionary" => "visit_dictionary",
+        "Pattern" => "visit_pattern",
+        "InstanceLiteral" => "visit_instance_literal",
+        "TermList" => "visit_list",
+        "Constraints" => "visit_partial",
+        "Rule" => "visit_rule",
+        "ExternalInstance" => "visit_external_instance",
+        "Parameter" => "visit_param",
+        _ => return None,
+    };
+    Some(syn::Ident::new(method, ident.span()))
+}
+
+/// An explicit `#[visit(call = "...")]`, if present, overriding inference.
+fn visit_call_attr(attrs: &[syn::Attribute]) -> Option<syn::Ident> {
+    for attr in attrs {
+        if !attr.path.is_ident("visit") {
+            continue;
+        }
+        if let Ok(Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested {
+                if let NestedMeta::Meta(Meta::NameValue(nv)) = nested {
+                    if nv.path.is_ident("call") {
+                        if let Lit::Str(s) = nv.lit {
+                            return Some(syn::Ident::new(&s.value(), s.span()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+fn walk_struct_fields(fields: &Fields) -> TokenStream2 {
+    let mut calls = Vec::new();
+    if let Fields::Named(named) = fields {
+        for field in &named.named {
+            let (shape, inner_ty) = shape_of(&field.ty);
+            let Some(method) = visit_call_attr(&field.attrs).or_else(|| inferred_call(inner_ty))
+            else {
+                continue;
+            };
+            let field_name = field.ident.as_ref().unwrap();
+            let call = match shape {
+                Shape::Bare => quote! { visitor.#method(&node.#field_name); },
+                Shape::Option => quote! {
+                    if let Some(inner) = &node.#field_name {
+                        visitor.#method(inner);
+                    }
+                },
+                Shape::Vec => quote! {
+                    for element in &node.#field_name {
+                        visitor.#method(element);
+                    }
+                },
+            };
+            calls.push(call);
+        }
+    }
+    quote! { #(#calls)* }
+}
+
+fn walk_enum_variants(
+    name: &syn::Ident,
+    variants: &syn::punctuated::Punctuated<syn::Variant, syn::token::Comma>,
+) -> TokenStream2 {
+    let mut arms = Vec::new();
+    for variant in variants {
+        let variant_name = &variant.ident;
+        let field_ty = match &variant.fields {
+            Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                Some(&unnamed.unnamed.first().unwrap().ty)
+            }
+            Fields::Unnamed(_) => {
+                panic!("#[derive(Visit)] only supports single-field tuple variants")
+            }
+            Fields::Unit | Fields::Named(_) => None,
+        };
+        let dispatch = field_ty.and_then(|ty| {
+            let (shape, inner_ty) = shape_of(ty);
+            let method = visit_call_attr(&variant.attrs).or_else(|| inferred_call(inner_ty))?;
+            Some((shape, method))
+        });
+        match dispatch {
+            Some((Shape::Bare, method)) => {
+                arms.push(quote! { #name::#variant_name(inner) => { visitor.#method(inner); } });
+            }
+            Some((Shape::Option, method)) => {
+                arms.push(quote! {
+                    #name::#variant_name(inner) => {
+                        if let Some(inner) = inner {
+                            visitor.#method(inner);
+                        }
+                    }
+                });
+            }
+            Some((Shape::Vec, method)) => {
+                arms.push(quote! {
+                    #name::#variant_name(inner) => {
+                        for element in inner {
+                            visitor.#method(element);
+                        }
+                    }
+                });
+            }
+            None => {
+                let pattern = match &variant.fields {
+                    Fields::Unit => quote! { #name::#variant_name },
+                    Fields::Named(_) => quote! { #name::#variant_name { .. } },
+                    Fields::Unnamed(_) => quote! { #name::#variant_name(..) },
+                };
+                arms.push(quote! { #pattern => {} });
+            }
+        }
+    }
+    quote! {
+        match node {
+            #(#arms)*
+        }
+    }
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() && i != 0 {
+            out.push('_');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}